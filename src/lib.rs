@@ -1,7 +1,20 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+use alloc::boxed::Box;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::convert::Infallible;
+
+#[cfg(feature = "std")]
 use std::io::{Read, Write};
+#[cfg(feature = "std")]
 use byteorder::{ReadBytesExt, LittleEndian, WriteBytesExt};
 use thiserror::Error;
 
+pub mod thumbnail;
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 /// XGCode header
 pub struct Header {
@@ -41,6 +54,79 @@ pub struct Header {
 
 }
 
+impl Header {
+
+    /// Regenerate the slicer-derived metadata fields by scanning the gcode
+    /// text, overwriting them in place.
+    ///
+    /// The scanner walks the program token by token — summing positive `E`
+    /// extrusion deltas per active tool (`T0`/`T1`) into the filament usages,
+    /// integrating move distance over the current feedrate into `print_time`,
+    /// and picking up temperatures (`M104`/`M109`, `M140`/`M190`) and the layer
+    /// height from layer-change comments. Unknown commands and malformed lines
+    /// are skipped so hand-edited or spliced gcode still yields a consistent
+    /// header. Fields the slicer owns (shells, speed, reserved words) are left
+    /// untouched.
+    #[cfg(feature = "std")]
+    pub fn recompute_from_gcode(&mut self, gcode: &[u8]) {
+        let mut tool = 0usize;        // active extruder
+        let mut e = [0f32; 2];        // last absolute E position per tool
+        let mut filament = [0f32; 2]; // accumulated usage per tool, mm
+        let mut pos = [0f32; 3];      // X, Y, Z
+        let mut feed = 0f32;          // mm/min
+        let mut seconds = 0f32;
+        let mut hotend = [0u16; 2];
+        let mut bed = 0u16;
+        let mut layer_um = 0u16;
+
+        for raw in gcode.split(|&b| b == b'\n') {
+            let line = match std::str::from_utf8(raw) { Ok(l) => l, Err(_) => continue };
+
+            if let Some(h) = layer_height_um(line) { layer_um = h; }
+
+            let code = line.split(';').next().unwrap_or("").trim();
+            let cmd = match code.split_whitespace().next() { Some(c) => c, None => continue };
+
+            if cmd.eq_ignore_ascii_case("T0") {
+                tool = 0;
+            } else if cmd.eq_ignore_ascii_case("T1") {
+                tool = 1;
+            } else if cmd.eq_ignore_ascii_case("G92") {
+                if let Some(v) = word(code, b'E') { e[tool] = v; }
+            } else if cmd.eq_ignore_ascii_case("G0") || cmd.eq_ignore_ascii_case("G1") {
+                if let Some(f) = word(code, b'F') { if f > 0.0 { feed = f; } }
+                let nx = word(code, b'X').unwrap_or(pos[0]);
+                let ny = word(code, b'Y').unwrap_or(pos[1]);
+                let nz = word(code, b'Z').unwrap_or(pos[2]);
+                let dist = ((nx - pos[0]).powi(2) + (ny - pos[1]).powi(2) + (nz - pos[2]).powi(2)).sqrt();
+                if feed > 0.0 && dist > 0.0 { seconds += dist / (feed / 60.0); }
+                pos = [nx, ny, nz];
+                if let Some(ev) = word(code, b'E') {
+                    let delta = ev - e[tool];
+                    if delta > 0.0 { filament[tool] += delta; }
+                    e[tool] = ev;
+                }
+            } else if cmd.eq_ignore_ascii_case("M104") || cmd.eq_ignore_ascii_case("M109") {
+                if let Some(s) = word(code, b'S') {
+                    let t = word(code, b'T').map(|v| v as usize).unwrap_or(tool);
+                    if t < 2 { hotend[t] = s as u16; }
+                }
+            } else if cmd.eq_ignore_ascii_case("M140") || cmd.eq_ignore_ascii_case("M190") {
+                if let Some(s) = word(code, b'S') { bed = s as u16; }
+            }
+        }
+
+        self.filament_0_usage = filament[0].round() as u32;
+        self.filament_1_usage = filament[1].round() as u32;
+        self.print_time = seconds.round() as u32;
+        if hotend[0] != 0 { self.extruder_0_temp = hotend[0]; }
+        if hotend[1] != 0 { self.extruder_1_temp = hotend[1]; }
+        if bed != 0 { self.hotbed_temp = bed; }
+        if layer_um != 0 { self.layer_height = layer_um; }
+    }
+
+}
+
 #[derive(Clone,Debug,Eq,PartialEq)]
 pub struct XGCode {
     /// XGcode header
@@ -61,61 +147,261 @@ pub struct XGCodeRef<'a> {
     pub gcode: &'a [u8],
 }
 
+/// Abstraction over the error type produced by an underlying reader or writer.
+///
+/// Decoupling the parser from `std::io` lets it run on an embedded transport
+/// (or a plain byte slice) while still letting `read` turn a short read into a
+/// typed [`Error::UnexpectedEof`] rather than an opaque IO error.
+pub trait IOError: core::error::Error {
+    /// Whether this error represents an unexpected end of input.
+    fn is_unexpected_eof(&self) -> bool;
+}
+
+impl IOError for Infallible {
+    fn is_unexpected_eof(&self) -> bool { match *self {} }
+}
 
+#[cfg(feature = "std")]
+impl IOError for std::io::Error {
+    fn is_unexpected_eof(&self) -> bool {
+        self.kind() == std::io::ErrorKind::UnexpectedEof
+    }
+}
 
 #[derive(Debug,Error)]
-pub enum Error {
+#[non_exhaustive]
+pub enum Error<E: IOError = Infallible> {
     #[error("Bad magic header")]    BadMagic(Box<[u8; 16]>),
     #[error("Bad header size")]     BadHeaderSize(u32),
     #[error("Thumb size negative")] ThumbSizeNegative(i32),
     #[error("GCode too big")]       ThumbnailTooLarge(usize),
     #[error("Second goffset not found")]  SecondGOffsetNotFound,
     #[error("Data in reserved field")]  DataInReservedField {offset: u16, value: u16},
-    #[error("IO error")]            IO(#[from] std::io::Error),
+    #[error("Truncated input at offset {offset}")]  Truncated {offset: usize},
+    #[error("Unexpected end of input")]  UnexpectedEof,
+    #[error("Thumbnail is not a BMP")]  ThumbnailNotBmp,
+    #[error("IO error")]            IO(#[from] E),
+}
+
+/// Options controlling how strictly a file is parsed.
+///
+/// The permissive default round-trips odd real-world files untouched; enabling
+/// [`strict`](ReadOptions::strict) makes tooling reject out-of-spec files up
+/// front.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct ReadOptions {
+    strict: bool,
+}
+
+impl ReadOptions {
+    /// Permissive options (the default).
+    pub fn new() -> Self { Self::default() }
+
+    /// Enable or disable strict validation of reserved fields, thumbnail magic
+    /// and the format version.
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
 }
 
 const XGCODE_MAGIC: &'static [u8; 16] = b"xgcode 1.0\n\0\0\0\0\0";
 const THUMB_OFFSET: u32 = 0x3A;
 
+/// Known-good magic headers accepted by strict validation. Only one format
+/// version has been observed so far, but the table leaves room for more.
+const KNOWN_MAGICS: &[&[u8; 16]] = &[XGCODE_MAGIC];
+
+/// Apply the strict-mode checks: a magic from [`KNOWN_MAGICS`], zeroed
+/// reserved fields, and a `BM`-prefixed thumbnail. The permissive path skips
+/// the last two; it already requires the magic to equal `XGCODE_MAGIC`.
+fn check_strict<E: IOError>(header: &Header, thumbnail: &[u8], magic: &[u8; 16]) -> Result<(), Error<E>> {
+    if !KNOWN_MAGICS.iter().any(|m| m.as_slice() == magic) {
+        return Err(Error::BadMagic(Box::new(*magic)));
+    }
+    if header.reserved0 != 0 {
+        return Err(Error::DataInReservedField { offset: 44, value: header.reserved0 });
+    }
+    if header.reserved1 != 0 {
+        return Err(Error::DataInReservedField { offset: 56, value: header.reserved1 });
+    }
+    if thumbnail.get(..2) != Some(b"BM".as_slice()) {
+        return Err(Error::ThumbnailNotBmp);
+    }
+    Ok(())
+}
+
+/// Extract the float value of the gcode word `letter` (e.g. `b'E'`) from a line
+/// of gcode, skipping the leading command token. Returns `None` if the word is
+/// absent or malformed.
+#[cfg(feature = "std")]
+fn word(code: &str, letter: u8) -> Option<f32> {
+    for tok in code.split_whitespace().skip(1) {
+        let bytes = tok.as_bytes();
+        if bytes.first().map_or(false, |b| b.eq_ignore_ascii_case(&letter)) {
+            if let Ok(v) = tok[1..].parse::<f32>() { return Some(v); }
+        }
+    }
+    None
+}
+
+/// Pull a layer height (in microns) out of a slicer layer-change comment such
+/// as `;layer_height:0.2` or `; layer height = 0.2`.
+#[cfg(feature = "std")]
+fn layer_height_um(line: &str) -> Option<u16> {
+    const KEY: &str = "layer_height";
+    let lower = line.to_ascii_lowercase();
+    let idx = lower.find(KEY).or_else(|| lower.find("layer height"))?;
+    let tail = &line[idx + KEY.len()..];
+    let num: String = tail.chars()
+        .skip_while(|c| !c.is_ascii_digit() && *c != '.')
+        .take_while(|c| c.is_ascii_digit() || *c == '.')
+        .collect();
+    let mm: f32 = num.parse().ok()?;
+    Some((mm * 1000.0).round() as u16)
+}
+
+/// A byte sink that can both append and patch previously-written bytes at a
+/// fixed offset, modelled on gimli's `Writer`.
+///
+/// The `write_at` primitive is what lets [`XGCodeRef::write_to`] emit the file
+/// with placeholder gcode offsets and back-patch them once the thumbnail and
+/// gcode have been streamed out, so the payload length need not be known up
+/// front. The little-endian helpers centralize the on-disk encoding.
+pub trait Writer {
+    /// Error produced by the underlying sink.
+    type Error: IOError;
+
+    /// Number of bytes written so far.
+    fn len(&self) -> usize;
+
+    /// Append `bytes` to the end of the output.
+    fn write(&mut self, bytes: &[u8]) -> Result<(), Self::Error>;
+
+    /// Overwrite `bytes.len()` bytes starting at `offset`, which must already
+    /// have been written.
+    fn write_at(&mut self, offset: usize, bytes: &[u8]) -> Result<(), Self::Error>;
+
+    /// Append a little-endian `u16`.
+    fn write_u16_le(&mut self, value: u16) -> Result<(), Self::Error> {
+        self.write(&value.to_le_bytes())
+    }
+
+    /// Append a little-endian `u32`.
+    fn write_u32_le(&mut self, value: u32) -> Result<(), Self::Error> {
+        self.write(&value.to_le_bytes())
+    }
+
+    /// Overwrite a little-endian `u32` at `offset`.
+    fn write_u32_le_at(&mut self, offset: usize, value: u32) -> Result<(), Self::Error> {
+        self.write_at(offset, &value.to_le_bytes())
+    }
+}
+
+/// Error returned by the in-memory [`Writer`] implementation.
+#[derive(Debug,Error)]
+pub enum WriterError {
+    #[error("write_at offset {offset} out of bounds (len {len})")]
+    OffsetOutOfBounds { offset: usize, len: usize },
+}
+
+impl IOError for WriterError {
+    fn is_unexpected_eof(&self) -> bool { false }
+}
+
+impl Writer for Vec<u8> {
+    type Error = WriterError;
+
+    fn len(&self) -> usize { Vec::len(self) }
+
+    fn write(&mut self, bytes: &[u8]) -> Result<(), WriterError> {
+        self.extend_from_slice(bytes);
+        Ok(())
+    }
+
+    fn write_at(&mut self, offset: usize, bytes: &[u8]) -> Result<(), WriterError> {
+        let len = Vec::len(self);
+        let slot = self.get_mut(offset..offset + bytes.len())
+            .ok_or(WriterError::OffsetOutOfBounds { offset, len })?;
+        slot.copy_from_slice(bytes);
+        Ok(())
+    }
+}
+
+/// Lift an underlying IO result into our [`Error`], turning a short read into
+/// the typed [`Error::UnexpectedEof`] via the [`IOError`] abstraction.
+#[cfg(feature = "std")]
+fn lift<T, E: IOError>(result: Result<T, E>) -> Result<T, Error<E>> {
+    result.map_err(|e| if e.is_unexpected_eof() { Error::UnexpectedEof } else { Error::IO(e) })
+}
+
+/// Read a little-endian `u16` at `offset`, bounds-checked against `buf`.
+fn read_u16_at(buf: &[u8], offset: usize) -> Result<u16, Error> {
+    let bytes = buf.get(offset..offset + 2).ok_or(Error::Truncated { offset })?;
+    Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+/// Read a little-endian `u32` at `offset`, bounds-checked against `buf`.
+fn read_u32_at(buf: &[u8], offset: usize) -> Result<u32, Error> {
+    let bytes = buf.get(offset..offset + 4).ok_or(Error::Truncated { offset })?;
+    Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
 impl XGCode {
 
-    pub fn read<R: Read>(mut source: R) -> Result<Self, Error> {
+    #[cfg(feature = "std")]
+    pub fn read<R: Read>(source: R) -> Result<Self, Error<std::io::Error>> {
+        Self::read_with(source, ReadOptions::new())
+    }
+
+    /// Parse a file, rejecting nonzero reserved fields, an unknown magic, or a
+    /// thumbnail that is not a BMP. Equivalent to `read_with(source,
+    /// ReadOptions::new().strict(true))`.
+    #[cfg(feature = "std")]
+    pub fn read_strict<R: Read>(source: R) -> Result<Self, Error<std::io::Error>> {
+        Self::read_with(source, ReadOptions::new().strict(true))
+    }
+
+    #[cfg(feature = "std")]
+    pub fn read_with<R: Read>(mut source: R, options: ReadOptions) -> Result<Self, Error<std::io::Error>> {
         let mut magic = [0; 16];
-        source.read_exact(&mut magic)?;
-        
+        lift(source.read_exact(&mut magic))?;
+
         if &magic != XGCODE_MAGIC { return Err(Error::BadMagic(Box::new(magic))) }
 
-        let thumb_offset = source.read_u32::<LittleEndian>()?;
+        let thumb_offset = lift(source.read_u32::<LittleEndian>())?;
         if thumb_offset != THUMB_OFFSET { return Err(Error::BadHeaderSize(thumb_offset)) }
 
-        let gcode_offset = source.read_u32::<LittleEndian>()?;
+        let gcode_offset = lift(source.read_u32::<LittleEndian>())?;
         let thumb_size = (gcode_offset as usize).checked_sub(THUMB_OFFSET as usize)
             .ok_or(Error::ThumbSizeNegative(gcode_offset as i32 - THUMB_OFFSET as i32))?;
 
 
-        let gcode_offset2 = source.read_u32::<LittleEndian>()?;
+        let gcode_offset2 = lift(source.read_u32::<LittleEndian>())?;
         if gcode_offset != gcode_offset2 { return Err(Error::SecondGOffsetNotFound)}
 
-        let print_time = source.read_u32::<LittleEndian>()?;
-        let filament_0_usage = source.read_u32::<LittleEndian>()?;
-        let filament_1_usage = source.read_u32::<LittleEndian>()?;
-        let multi_extruder_type = source.read_u16::<LittleEndian>()?;
-        let layer_height = source.read_u16::<LittleEndian>()?;
-        let reserved0 = source.read_u16::<LittleEndian>()?;
-        let perimeter_shells = source.read_u16::<LittleEndian>()?;
-        let print_speed = source.read_u16::<LittleEndian>()?;
-        let hotbed_temp = source.read_u16::<LittleEndian>()?;
-        let extruder_0_temp = source.read_u16::<LittleEndian>()?;
-        let extruder_1_temp = source.read_u16::<LittleEndian>()?;
-        let reserved1 = source.read_u16::<LittleEndian>()?;
+        let print_time = lift(source.read_u32::<LittleEndian>())?;
+        let filament_0_usage = lift(source.read_u32::<LittleEndian>())?;
+        let filament_1_usage = lift(source.read_u32::<LittleEndian>())?;
+        let multi_extruder_type = lift(source.read_u16::<LittleEndian>())?;
+        let layer_height = lift(source.read_u16::<LittleEndian>())?;
+        let reserved0 = lift(source.read_u16::<LittleEndian>())?;
+        let perimeter_shells = lift(source.read_u16::<LittleEndian>())?;
+        let print_speed = lift(source.read_u16::<LittleEndian>())?;
+        let hotbed_temp = lift(source.read_u16::<LittleEndian>())?;
+        let extruder_0_temp = lift(source.read_u16::<LittleEndian>())?;
+        let extruder_1_temp = lift(source.read_u16::<LittleEndian>())?;
+        let reserved1 = lift(source.read_u16::<LittleEndian>())?;
 
         let header = Header { print_time, filament_0_usage, filament_1_usage, multi_extruder_type, layer_height, perimeter_shells, print_speed, hotbed_temp, extruder_0_temp, extruder_1_temp, reserved0, reserved1 };
 
         let mut thumbnail = vec![0; thumb_size];
-        source.read_exact(&mut thumbnail)?;
+        lift(source.read_exact(&mut thumbnail))?;
 
         let mut gcode = vec![];
-        source.read_to_end(&mut gcode)?;
+        lift(source.read_to_end(&mut gcode))?;
+
+        if options.strict { check_strict(&header, &thumbnail, &magic)?; }
 
         Ok(XGCode{ header, thumbnail, gcode })
 
@@ -125,7 +411,31 @@ impl XGCode {
         XGCodeRef { header: self.header, thumbnail: &self.thumbnail[..], gcode: &self.gcode[..] }
     }
 
-    pub fn write<W: Write>(&self, writer: W) -> Result<(), Error> {
+    /// Rescan the embedded gcode and regenerate the header's derived metadata.
+    ///
+    /// Useful after splicing or editing the gcode, to keep the header in sync
+    /// without re-running the slicer. See [`Header::recompute_from_gcode`].
+    #[cfg(feature = "std")]
+    pub fn update_header(&mut self) {
+        self.header.recompute_from_gcode(&self.gcode);
+    }
+
+    /// Decode the embedded thumbnail into 80×60 top-to-bottom RGB triplets.
+    pub fn decode_thumbnail(&self) -> Result<Vec<u8>, thumbnail::ThumbnailError> {
+        thumbnail::decode(&self.thumbnail)
+    }
+
+    /// Resample an arbitrary RGB framebuffer down to the required 80×60 preview
+    /// and store it as a spec-compliant BMP thumbnail.
+    pub fn set_thumbnail_from_rgb(&mut self, width: u32, height: u32, rgb: &[u8])
+        -> Result<(), thumbnail::ThumbnailError>
+    {
+        self.thumbnail = thumbnail::encode_from_rgb(width, height, rgb)?;
+        Ok(())
+    }
+
+    #[cfg(feature = "std")]
+    pub fn write<W: Write>(&self, writer: W) -> Result<(), Error<std::io::Error>> {
         self.as_ref().write(writer)
 
 
@@ -134,7 +444,119 @@ impl XGCode {
 }
 
 impl<'a> XGCodeRef<'a> {
-    fn write<W: Write>(&self, mut writer: W) -> Result<(), Error> {
+
+    /// Parse an `XGCodeRef` directly out of an in-memory buffer, returning the
+    /// thumbnail and gcode as borrowed sub-slices of `buf` with no copying.
+    ///
+    /// This is the zero-allocation counterpart to [`XGCode::read`], suited to
+    /// memory-mapped or `include_bytes!`-backed callers that already hold the
+    /// whole file and only want to inspect the header and thumbnail. It is the
+    /// entry point available under `#![no_std]`.
+    pub fn read(buf: &'a [u8]) -> Result<XGCodeRef<'a>, Error> {
+        Self::read_with(buf, ReadOptions::new())
+    }
+
+    /// Strict counterpart to [`XGCodeRef::read`]; see [`XGCode::read_strict`].
+    pub fn read_strict(buf: &'a [u8]) -> Result<XGCodeRef<'a>, Error> {
+        Self::read_with(buf, ReadOptions::new().strict(true))
+    }
+
+    /// Parse an `XGCodeRef` out of `buf` honouring the given [`ReadOptions`].
+    pub fn read_with(buf: &'a [u8], options: ReadOptions) -> Result<XGCodeRef<'a>, Error> {
+        let magic = buf.get(..16).ok_or(Error::Truncated { offset: 0 })?;
+        if magic != XGCODE_MAGIC {
+            let mut m = [0; 16];
+            m.copy_from_slice(magic);
+            return Err(Error::BadMagic(Box::new(m)));
+        }
+
+        let thumb_offset = read_u32_at(buf, 16)?;
+        if thumb_offset != THUMB_OFFSET { return Err(Error::BadHeaderSize(thumb_offset)) }
+
+        let gcode_offset = read_u32_at(buf, 20)?;
+        let thumb_size = (gcode_offset as usize).checked_sub(THUMB_OFFSET as usize)
+            .ok_or(Error::ThumbSizeNegative(gcode_offset as i32 - THUMB_OFFSET as i32))?;
+
+        let gcode_offset2 = read_u32_at(buf, 24)?;
+        if gcode_offset != gcode_offset2 { return Err(Error::SecondGOffsetNotFound) }
+
+        let print_time = read_u32_at(buf, 28)?;
+        let filament_0_usage = read_u32_at(buf, 32)?;
+        let filament_1_usage = read_u32_at(buf, 36)?;
+        let multi_extruder_type = read_u16_at(buf, 40)?;
+        let layer_height = read_u16_at(buf, 42)?;
+        let reserved0 = read_u16_at(buf, 44)?;
+        let perimeter_shells = read_u16_at(buf, 46)?;
+        let print_speed = read_u16_at(buf, 48)?;
+        let hotbed_temp = read_u16_at(buf, 50)?;
+        let extruder_0_temp = read_u16_at(buf, 52)?;
+        let extruder_1_temp = read_u16_at(buf, 54)?;
+        let reserved1 = read_u16_at(buf, 56)?;
+
+        let header = Header { print_time, filament_0_usage, filament_1_usage, multi_extruder_type, layer_height, perimeter_shells, print_speed, hotbed_temp, extruder_0_temp, extruder_1_temp, reserved0, reserved1 };
+
+        let thumb_start = THUMB_OFFSET as usize;
+        let thumbnail = buf.get(thumb_start..gcode_offset as usize)
+            .ok_or(Error::Truncated { offset: thumb_start })?;
+        let gcode = buf.get(gcode_offset as usize..)
+            .ok_or(Error::Truncated { offset: gcode_offset as usize })?;
+
+        if options.strict {
+            let mut m = [0; 16];
+            m.copy_from_slice(magic);
+            check_strict(&header, thumbnail, &m)?;
+        }
+
+        Ok(XGCodeRef { header, thumbnail, gcode })
+    }
+
+    /// Encode the file into a [`Writer`], streaming the thumbnail and gcode and
+    /// back-patching the two gcode-offset fields once the payload length is
+    /// known.
+    ///
+    /// Unlike [`XGCodeRef::write`], the header offsets are not computed from
+    /// `thumbnail.len()`: they start as zero placeholders and are patched in
+    /// place with the position actually reached after the thumbnail. The
+    /// thumbnail and gcode are still taken as in-memory slices; `thumbnail.len()`
+    /// is only used to reject a thumbnail too large for a `u32` offset.
+    pub fn write_to<W: Writer>(&self, w: &mut W) -> Result<(), Error<W::Error>> {
+        if THUMB_OFFSET as usize + self.thumbnail.len() > (u32::MAX as usize) {
+            return Err(Error::ThumbnailTooLarge(self.thumbnail.len()));
+        }
+
+        let start = w.len();
+        w.write(XGCODE_MAGIC)?;
+        w.write_u32_le(THUMB_OFFSET)?;
+        let goffset_field_0 = w.len();
+        w.write_u32_le(0)?;                 // placeholder, back-patched below
+        let goffset_field_1 = w.len();
+        w.write_u32_le(0)?;                 // second field, also back-patched
+
+        w.write_u32_le(self.header.print_time)?;
+        w.write_u32_le(self.header.filament_0_usage)?;
+        w.write_u32_le(self.header.filament_1_usage)?;
+        w.write_u16_le(self.header.multi_extruder_type)?;
+        w.write_u16_le(self.header.layer_height)?;
+        w.write_u16_le(self.header.reserved0)?;
+        w.write_u16_le(self.header.perimeter_shells)?;
+        w.write_u16_le(self.header.print_speed)?;
+        w.write_u16_le(self.header.hotbed_temp)?;
+        w.write_u16_le(self.header.extruder_0_temp)?;
+        w.write_u16_le(self.header.extruder_1_temp)?;
+        w.write_u16_le(self.header.reserved1)?;
+
+        w.write(self.thumbnail)?;
+        let gcode_offset = (w.len() - start) as u32;  // == THUMB_OFFSET + thumbnail.len()
+        w.write(self.gcode)?;
+
+        w.write_u32_le_at(goffset_field_0, gcode_offset)?;
+        w.write_u32_le_at(goffset_field_1, gcode_offset)?;
+
+        Ok(())
+    }
+
+    #[cfg(feature = "std")]
+    fn write<W: Write>(&self, mut writer: W) -> Result<(), Error<std::io::Error>> {
 
         let gcode_offset = THUMB_OFFSET as usize + self.thumbnail.len();
         if gcode_offset > (u32::MAX as usize) { return Err(Error::ThumbnailTooLarge(self.thumbnail.len()))}
@@ -164,9 +586,9 @@ impl<'a> XGCodeRef<'a> {
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "std"))]
 mod tests {
-    
+
 
     use std::{fs::File, io::Write};
 
@@ -188,4 +610,97 @@ mod tests {
 
     }
 
+    #[test]
+    fn test_borrowing_reader() {
+        use crate::XGCodeRef;
+
+        let file = include_bytes!("../test/20mm_Box.gx");
+        let owned = XGCode::read(&mut &file[..]).unwrap();
+        let borrowed = XGCodeRef::read(&file[..]).unwrap();
+
+        assert_eq!(borrowed.header, owned.header);
+        assert_eq!(borrowed.thumbnail, &owned.thumbnail[..]);
+        assert_eq!(borrowed.gcode, &owned.gcode[..]);
+    }
+
+    #[test]
+    fn test_writer_backpatch() {
+        let file = include_bytes!("../test/20mm_Box.gx");
+        let parsed = XGCode::read(&mut &file[..]).unwrap();
+
+        let mut out = vec![];
+        parsed.as_ref().write_to(&mut out).unwrap();
+
+        assert_eq!(file, &out[..]);
+    }
+
+    #[test]
+    fn test_thumbnail_roundtrip() {
+        use crate::thumbnail::{THUMB_WIDTH, THUMB_HEIGHT};
+
+        let file = include_bytes!("../test/20mm_Box.gx");
+        let mut parsed = XGCode::read(&mut &file[..]).unwrap();
+
+        let rgb = parsed.decode_thumbnail().unwrap();
+        assert_eq!(rgb.len(), THUMB_WIDTH * THUMB_HEIGHT * 3);
+
+        // Re-encoding the decoded pixels at native size is a lossless round-trip.
+        parsed.set_thumbnail_from_rgb(THUMB_WIDTH as u32, THUMB_HEIGHT as u32, &rgb).unwrap();
+        assert_eq!(parsed.decode_thumbnail().unwrap(), rgb);
+    }
+
+    #[test]
+    fn test_strict_validation() {
+        use crate::Error;
+
+        let file = include_bytes!("../test/20mm_Box.gx");
+
+        // The stock sample is spec-compliant and passes strict parsing.
+        let parsed = XGCode::read_strict(&mut &file[..]).unwrap();
+
+        // A nonzero reserved field is rejected in strict mode but not permissive.
+        let mut tampered = parsed.clone();
+        tampered.header.reserved0 = 0x1234;
+        let mut bytes = vec![];
+        tampered.write(&mut bytes).unwrap();
+
+        XGCode::read(&mut &bytes[..]).unwrap(); // permissive still accepts it
+        match XGCode::read_strict(&mut &bytes[..]) {
+            Err(Error::DataInReservedField { value, .. }) => assert_eq!(value, 0x1234),
+            other => panic!("expected DataInReservedField, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_recompute_from_gcode() {
+        use crate::Header;
+
+        let gcode = b"\
+;layer_height:0.2\n\
+M140 S60\n\
+M104 S210 T0\n\
+T0\n\
+G92 E0\n\
+G1 F1800 X0 Y0 Z0\n\
+G1 X10 Y0 E5\n\
+T1\n\
+G92 E0\n\
+G1 X20 Y0 E3\n\
+";
+
+        let mut h = Header {
+            print_time: 0, filament_0_usage: 0, filament_1_usage: 0,
+            multi_extruder_type: 0, layer_height: 0, reserved0: 0,
+            perimeter_shells: 0, print_speed: 0, hotbed_temp: 0,
+            extruder_0_temp: 0, extruder_1_temp: 0, reserved1: 0,
+        };
+        h.recompute_from_gcode(gcode);
+
+        assert_eq!(h.filament_0_usage, 5);
+        assert_eq!(h.filament_1_usage, 3);
+        assert_eq!(h.extruder_0_temp, 210);
+        assert_eq!(h.hotbed_temp, 60);
+        assert_eq!(h.layer_height, 200);
+    }
+
 }
@@ -0,0 +1,155 @@
+//! Decoding and encoding of the 80×60 BMP thumbnail embedded in a `.gx` file.
+//!
+//! FlashPrint stores the print preview as a 24-bit, bottom-up Windows BMP with
+//! fixed 80×60 dimensions. This module turns that opaque blob into plain RGB
+//! pixels and, going the other way, resamples an arbitrary framebuffer down to
+//! a spec-compliant thumbnail so slicer integrations can hand in a PNG or raw
+//! buffer without hand-crafting the BMP themselves.
+
+use alloc::vec;
+use alloc::vec::Vec;
+use thiserror::Error;
+
+/// Width of an XGCode thumbnail, in pixels.
+pub const THUMB_WIDTH: usize = 80;
+/// Height of an XGCode thumbnail, in pixels.
+pub const THUMB_HEIGHT: usize = 60;
+
+const FILE_HEADER_LEN: usize = 14;
+const INFO_HEADER_LEN: usize = 40;
+const BITS_PER_PIXEL: usize = 24;
+
+/// Bytes per padded BMP row for the fixed thumbnail width (rounded up to 4).
+const ROW_STRIDE: usize = (THUMB_WIDTH * BITS_PER_PIXEL / 8 + 3) & !3;
+
+/// Exact size, in bytes, of a well-formed thumbnail BMP.
+pub const fn required_bytes() -> usize {
+    FILE_HEADER_LEN + INFO_HEADER_LEN + ROW_STRIDE * THUMB_HEIGHT
+}
+
+#[derive(Debug,Error)]
+pub enum ThumbnailError {
+    #[error("not a BMP file (missing BM magic)")]
+    BadMagic,
+    #[error("buffer too small: need at least {need} bytes, got {got}")]
+    TooSmall { need: usize, got: usize },
+    #[error("unsupported dimensions {width}x{height}, expected 80x60")]
+    BadDimensions { width: i32, height: i32 },
+    #[error("unsupported pixel format: {bits}-bit, compression {compression}")]
+    BadPixelFormat { bits: u16, compression: u32 },
+    #[error("RGB buffer length {got} does not match {width}x{height}x3")]
+    BadRgbLength { width: u32, height: u32, got: usize },
+}
+
+fn u16_le(b: &[u8], o: usize) -> u16 { u16::from_le_bytes([b[o], b[o + 1]]) }
+fn u32_le(b: &[u8], o: usize) -> u32 { u32::from_le_bytes([b[o], b[o + 1], b[o + 2], b[o + 3]]) }
+fn i32_le(b: &[u8], o: usize) -> i32 { u32_le(b, o) as i32 }
+
+fn put_u16(b: &mut [u8], o: usize, v: u16) { b[o..o + 2].copy_from_slice(&v.to_le_bytes()); }
+fn put_u32(b: &mut [u8], o: usize, v: u32) { b[o..o + 4].copy_from_slice(&v.to_le_bytes()); }
+fn put_i32(b: &mut [u8], o: usize, v: i32) { b[o..o + 4].copy_from_slice(&v.to_le_bytes()); }
+
+/// Decode the embedded BMP thumbnail into 80×60 top-to-bottom RGB triplets.
+///
+/// Rejects buffers that are undersized, lack the `BM` magic, are not 24-bit
+/// uncompressed, or carry unexpected dimensions — returning a typed
+/// [`ThumbnailError`] rather than panicking.
+pub fn decode(buf: &[u8]) -> Result<Vec<u8>, ThumbnailError> {
+    if buf.len() < FILE_HEADER_LEN + INFO_HEADER_LEN {
+        return Err(ThumbnailError::TooSmall { need: required_bytes(), got: buf.len() });
+    }
+    if &buf[..2] != b"BM" { return Err(ThumbnailError::BadMagic); }
+
+    let pixel_offset = u32_le(buf, 10) as usize;
+    let width = i32_le(buf, 18);
+    let height = i32_le(buf, 22);
+    let bits = u16_le(buf, 28);
+    let compression = u32_le(buf, 30);
+
+    if bits as usize != BITS_PER_PIXEL || compression != 0 {
+        return Err(ThumbnailError::BadPixelFormat { bits, compression });
+    }
+    if width != THUMB_WIDTH as i32 || height.unsigned_abs() != THUMB_HEIGHT as u32 {
+        return Err(ThumbnailError::BadDimensions { width, height });
+    }
+
+    let need = pixel_offset + ROW_STRIDE * THUMB_HEIGHT;
+    if buf.len() < need {
+        return Err(ThumbnailError::TooSmall { need, got: buf.len() });
+    }
+
+    // A positive height means the rows are stored bottom-up.
+    let top_down = height < 0;
+    let mut rgb = vec![0u8; THUMB_WIDTH * THUMB_HEIGHT * 3];
+    for row in 0..THUMB_HEIGHT {
+        let src_row = if top_down { row } else { THUMB_HEIGHT - 1 - row };
+        let src = pixel_offset + src_row * ROW_STRIDE;
+        for col in 0..THUMB_WIDTH {
+            let p = src + col * 3;
+            let dst = (row * THUMB_WIDTH + col) * 3;
+            rgb[dst]     = buf[p + 2]; // R (BMP stores BGR)
+            rgb[dst + 1] = buf[p + 1]; // G
+            rgb[dst + 2] = buf[p];     // B
+        }
+    }
+    Ok(rgb)
+}
+
+/// Nearest-neighbour resample arbitrary `width`×`height` top-down RGB down to
+/// the fixed 80×60 thumbnail resolution.
+pub fn resample(width: u32, height: u32, rgb: &[u8]) -> Result<Vec<u8>, ThumbnailError> {
+    let (w, h) = (width as usize, height as usize);
+    if width == 0 || height == 0 || rgb.len() != w * h * 3 {
+        return Err(ThumbnailError::BadRgbLength { width, height, got: rgb.len() });
+    }
+
+    let mut out = vec![0u8; THUMB_WIDTH * THUMB_HEIGHT * 3];
+    for y in 0..THUMB_HEIGHT {
+        let sy = y * h / THUMB_HEIGHT;
+        for x in 0..THUMB_WIDTH {
+            let sx = x * w / THUMB_WIDTH;
+            let s = (sy * w + sx) * 3;
+            let d = (y * THUMB_WIDTH + x) * 3;
+            out[d..d + 3].copy_from_slice(&rgb[s..s + 3]);
+        }
+    }
+    Ok(out)
+}
+
+/// Encode 80×60 top-to-bottom RGB triplets into a spec-compliant bottom-up BMP.
+fn encode(rgb: &[u8]) -> Vec<u8> {
+    let pixel_offset = FILE_HEADER_LEN + INFO_HEADER_LEN;
+    let mut out = vec![0u8; required_bytes()];
+
+    // BITMAPFILEHEADER
+    out[0] = b'B';
+    out[1] = b'M';
+    put_u32(&mut out, 2, required_bytes() as u32);
+    put_u32(&mut out, 10, pixel_offset as u32);
+
+    // BITMAPINFOHEADER
+    put_u32(&mut out, 14, INFO_HEADER_LEN as u32);
+    put_i32(&mut out, 18, THUMB_WIDTH as i32);
+    put_i32(&mut out, 22, THUMB_HEIGHT as i32); // positive => bottom-up
+    put_u16(&mut out, 26, 1);                   // colour planes
+    put_u16(&mut out, 28, BITS_PER_PIXEL as u16);
+    put_u32(&mut out, 34, (ROW_STRIDE * THUMB_HEIGHT) as u32);
+
+    for row in 0..THUMB_HEIGHT {
+        let src_row = THUMB_HEIGHT - 1 - row; // input top-down, BMP bottom-up
+        let dst = pixel_offset + row * ROW_STRIDE;
+        for col in 0..THUMB_WIDTH {
+            let s = (src_row * THUMB_WIDTH + col) * 3;
+            let d = dst + col * 3;
+            out[d]     = rgb[s + 2]; // B
+            out[d + 1] = rgb[s + 1]; // G
+            out[d + 2] = rgb[s];     // R
+        }
+    }
+    out
+}
+
+/// Resample an arbitrary RGB framebuffer and encode it as a thumbnail BMP.
+pub fn encode_from_rgb(width: u32, height: u32, rgb: &[u8]) -> Result<Vec<u8>, ThumbnailError> {
+    Ok(encode(&resample(width, height, rgb)?))
+}